@@ -0,0 +1,182 @@
+use solana_hash::Hash;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::smt::SparseMerkleTree;
+
+/// A fork-aware layer over [`SparseMerkleTree`] that lets callers branch state
+/// per slot and later collapse it, mirroring the bank's `merge_parents`/squash
+/// semantics.
+///
+/// Each fork keeps its own overlay of changed leaves plus an `Arc` chain of
+/// ancestor forks. Reads resolve against the overlay first and then walk the
+/// ancestors, so a speculative fork sees committed parent state without copying
+/// it. Only a rooted fork exposes a verifiable root.
+#[derive(Clone, Debug)]
+pub struct ForkedMerkleState {
+    slot: u64,
+    overlay: HashMap<Pubkey, Account>,
+    parent: Option<Arc<ForkedMerkleState>>,
+    tree: SparseMerkleTree,
+    rooted: bool,
+}
+
+impl ForkedMerkleState {
+    /// Create a rooted base fork for `slot`; writes are committed straight into
+    /// its tree.
+    pub fn new(slot: u64) -> Self {
+        ForkedMerkleState {
+            slot,
+            overlay: HashMap::new(),
+            parent: None,
+            tree: SparseMerkleTree::new(),
+            rooted: true,
+        }
+    }
+
+    /// Branch a new speculative fork for `slot` off `parent`. The child shares
+    /// the parent's state through the ancestor chain and buffers its own writes
+    /// in an overlay until it is squashed.
+    pub fn new_from_parent(parent: &Arc<ForkedMerkleState>, slot: u64) -> Self {
+        ForkedMerkleState {
+            slot,
+            overlay: HashMap::new(),
+            parent: Some(parent.clone()),
+            tree: SparseMerkleTree::new(),
+            rooted: false,
+        }
+    }
+
+    /// The slot this fork represents.
+    pub fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    /// Record a write in this fork. A rooted fork also commits it into its tree;
+    /// a speculative fork only buffers it until [`squash`].
+    pub fn insert(&mut self, pubkey: Pubkey, account: &Account) {
+        self.overlay.insert(pubkey, account.clone());
+        if self.rooted {
+            self.tree.insert(pubkey, account);
+        }
+    }
+
+    /// Resolve an account by searching this fork's overlay and then walking the
+    /// ancestor chain, returning the most recent write.
+    pub fn get(&self, pubkey: &Pubkey) -> Option<Account> {
+        if let Some(account) = self.overlay.get(pubkey) {
+            return Some(account.clone());
+        }
+        match &self.parent {
+            Some(parent) => parent.get(pubkey),
+            None => None,
+        }
+    }
+
+    /// Collapse this fork into a root. Every leaf visible through the ancestor
+    /// chain is pulled down into the overlay (local writes override ancestors),
+    /// the rooted tree is rebuilt, and any zero-lamport empty-data account is
+    /// purged so it folds to the empty-leaf placeholder and does not contribute
+    /// to the root. The ancestor chain is then discarded.
+    pub fn squash(&mut self) {
+        // Collect ancestors newest-first so nearer writes win over older ones.
+        let mut ancestor = self.parent.clone();
+        while let Some(fork) = ancestor {
+            for (pubkey, account) in &fork.overlay {
+                self.overlay.entry(*pubkey).or_insert_with(|| account.clone());
+            }
+            ancestor = fork.parent.clone();
+        }
+
+        // Drop zero-lamport empty-data accounts entirely, exactly as the bank
+        // purges zero-balance accounts when a fork becomes rooted.
+        self.overlay
+            .retain(|_, account| !(account.lamports == 0 && account.data.is_empty()));
+
+        let mut tree = SparseMerkleTree::new();
+        for (pubkey, account) in &self.overlay {
+            tree.insert(*pubkey, account);
+        }
+
+        self.tree = tree;
+        self.parent = None;
+        self.rooted = true;
+    }
+
+    /// The root of this fork's tree (only meaningful once rooted).
+    pub fn root(&self) -> Hash {
+        self.tree.get_root()
+    }
+
+    /// Verify `root` against this fork, succeeding only on a rooted (finalized)
+    /// fork so callers can distinguish speculative from committed state.
+    pub fn verify_root(&self, root: &Hash) -> bool {
+        self.rooted && &self.tree.get_root() == root
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::signature::{Keypair, Signer};
+
+    fn create_example_account(pubkey: Pubkey) -> Account {
+        Account {
+            lamports: 1000,
+            data: vec![1, 2, 3, 4],
+            executable: false,
+            rent_epoch: 1,
+            owner: pubkey,
+        }
+    }
+
+    #[test]
+    fn test_fork_resolves_through_ancestors() {
+        let mut base = ForkedMerkleState::new(0);
+        let parent_key = Keypair::new().pubkey();
+        base.insert(parent_key, &create_example_account(parent_key));
+
+        let base = Arc::new(base);
+        let mut child = ForkedMerkleState::new_from_parent(&base, 1);
+
+        let child_key = Keypair::new().pubkey();
+        child.insert(child_key, &create_example_account(child_key));
+
+        assert!(child.get(&parent_key).is_some(), "Child should see ancestor state");
+        assert!(child.get(&child_key).is_some(), "Child should see its own overlay");
+        assert!(!child.verify_root(&child.root()), "Speculative fork must not verify a root");
+    }
+
+    #[test]
+    fn test_squash_roots_and_purges_zero_lamport() {
+        let mut base = ForkedMerkleState::new(0);
+        let kept = Keypair::new().pubkey();
+        base.insert(kept, &create_example_account(kept));
+        let base = Arc::new(base);
+
+        let mut child = ForkedMerkleState::new_from_parent(&base, 1);
+        let purged = Keypair::new().pubkey();
+        child.insert(
+            purged,
+            &Account {
+                lamports: 0,
+                data: vec![],
+                executable: false,
+                rent_epoch: 0,
+                owner: purged,
+            },
+        );
+
+        child.squash();
+        println!("🧹 Rooted fork root after squash: {:?}", child.root());
+        assert!(child.verify_root(&child.root()), "Squashed fork should verify its root");
+        assert!(child.get(&kept).is_some(), "Squash should pull ancestor leaves down");
+        assert!(child.get(&purged).is_none(), "Zero-lamport account should be purged");
+
+        // The rooted fork matches a tree built directly from the kept account.
+        let mut expected = SparseMerkleTree::new();
+        expected.insert(kept, &create_example_account(kept));
+        assert_eq!(child.root(), expected.get_root(), "Root should exclude the purged account");
+    }
+}
@@ -0,0 +1,4 @@
+pub mod forked_state;
+pub mod persistent_store;
+pub mod smt;
+pub mod smt_solana;
@@ -0,0 +1,240 @@
+use solana_hash::Hash;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+use memmap2::MmapMut;
+
+/// On-disk append log record: `leaf_key (32) || account_hash (32) || write_version (8)`.
+const RECORD_SIZE: usize = 32 + 32 + 8;
+/// Bytes reserved at the head of each append file for its used-length header.
+const HEADER_SIZE: usize = 8;
+/// Fixed capacity of a single append file before it rolls over to the next one.
+const FILE_CAPACITY: usize = HEADER_SIZE + RECORD_SIZE * 65_536;
+
+/// One memory-mapped append-only file, modeled on Solana's `AppendVec`.
+///
+/// The first [`HEADER_SIZE`] bytes hold the number of record bytes currently in
+/// use so the file is self-describing on recovery; records follow contiguously.
+#[derive(Debug)]
+struct AppendVec {
+    mmap: MmapMut,
+    used: usize,
+    _file: File,
+}
+
+impl AppendVec {
+    /// Create a fresh zero-filled append file of [`FILE_CAPACITY`] bytes.
+    fn create(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(path)?;
+        file.set_len(FILE_CAPACITY as u64)?;
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+        Ok(AppendVec {
+            mmap,
+            used: 0,
+            _file: file,
+        })
+    }
+
+    /// Re-open an existing append file and read back its used length.
+    fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mmap = unsafe { MmapMut::map_mut(&file)? };
+        let mut header = [0u8; HEADER_SIZE];
+        header.copy_from_slice(&mmap[..HEADER_SIZE]);
+        let used = u64::from_le_bytes(header) as usize;
+        Ok(AppendVec {
+            mmap,
+            used,
+            _file: file,
+        })
+    }
+
+    fn remaining(&self) -> usize {
+        FILE_CAPACITY - HEADER_SIZE - self.used
+    }
+
+    /// Append one record, returning the record offset within the file. The
+    /// caller guarantees there is room (see [`AppendVec::remaining`]).
+    fn append(&mut self, leaf_key: &Hash, account_hash: &Hash, write_version: u64) -> usize {
+        let offset = HEADER_SIZE + self.used;
+        self.mmap[offset..offset + 32].copy_from_slice(leaf_key.as_ref());
+        self.mmap[offset + 32..offset + 64].copy_from_slice(account_hash.as_ref());
+        self.mmap[offset + 64..offset + RECORD_SIZE]
+            .copy_from_slice(&write_version.to_le_bytes());
+        self.used += RECORD_SIZE;
+        self.mmap[..HEADER_SIZE].copy_from_slice(&(self.used as u64).to_le_bytes());
+        offset
+    }
+
+    /// Decode the record stored at `offset`.
+    fn read(&self, offset: usize) -> (Hash, Hash, u64) {
+        let mut key = [0u8; 32];
+        let mut value = [0u8; 32];
+        let mut version = [0u8; 8];
+        key.copy_from_slice(&self.mmap[offset..offset + 32]);
+        value.copy_from_slice(&self.mmap[offset + 32..offset + 64]);
+        version.copy_from_slice(&self.mmap[offset + 64..offset + RECORD_SIZE]);
+        (
+            Hash::new_from_array(key),
+            Hash::new_from_array(value),
+            u64::from_le_bytes(version),
+        )
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.mmap.flush()
+    }
+}
+
+/// Persistent backing store for leaf updates, modeled on Solana's accounts_db.
+///
+/// Each committed leaf is append-serialized into one or more memory-mapped
+/// files while an in-memory index points at the latest write for every leaf
+/// key. A single monotonically increasing [`write_version`] tags every record
+/// so the index can be rebuilt on restart by keeping, per key, only the highest
+/// version seen. The design supports one writer with concurrent readers: each
+/// append file carries its own lock, so the `files` Vec lock is taken only
+/// briefly to resolve a file handle and is never held across the mmap write —
+/// a reader of one file never blocks on an append to another.
+#[derive(Debug)]
+pub struct PersistentStore {
+    path: PathBuf,
+    files: RwLock<Vec<Arc<RwLock<AppendVec>>>>,
+    index: RwLock<HashMap<Hash, (usize, usize)>>,
+    write_version: AtomicU64,
+}
+
+impl PersistentStore {
+    fn file_path(dir: &Path, file_id: usize) -> PathBuf {
+        dir.join(format!("append-{file_id}.log"))
+    }
+
+    /// Open (creating if necessary) the store rooted at `path`, replaying any
+    /// existing append logs to rebuild the index and the write-version counter.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        fs::create_dir_all(&path)?;
+
+        let mut files = Vec::new();
+        while Self::file_path(&path, files.len()).exists() {
+            let file = AppendVec::open(&Self::file_path(&path, files.len()))?;
+            files.push(Arc::new(RwLock::new(file)));
+        }
+        if files.is_empty() {
+            let file = AppendVec::create(&Self::file_path(&path, 0))?;
+            files.push(Arc::new(RwLock::new(file)));
+        }
+
+        let store = PersistentStore {
+            path,
+            files: RwLock::new(files),
+            index: RwLock::new(HashMap::new()),
+            write_version: AtomicU64::new(0),
+        };
+        store.rebuild_index();
+        Ok(store)
+    }
+
+    /// Scan every append log and keep, per leaf key, only the record with the
+    /// highest write version. Also advances the write-version counter past the
+    /// largest version observed.
+    fn rebuild_index(&self) {
+        let files = self.files.read().unwrap();
+        let mut index: HashMap<Hash, (usize, usize)> = HashMap::new();
+        let mut versions: HashMap<Hash, u64> = HashMap::new();
+        let mut max_version = 0u64;
+
+        for (file_id, file) in files.iter().enumerate() {
+            let file = file.read().unwrap();
+            let mut offset = HEADER_SIZE;
+            let end = HEADER_SIZE + file.used;
+            while offset < end {
+                let (leaf_key, _account_hash, write_version) = file.read(offset);
+                if versions.get(&leaf_key).is_none_or(|v| write_version >= *v) {
+                    versions.insert(leaf_key, write_version);
+                    index.insert(leaf_key, (file_id, offset));
+                }
+                max_version = max_version.max(write_version);
+                offset += RECORD_SIZE;
+            }
+        }
+
+        *self.index.write().unwrap() = index;
+        self.write_version.store(max_version, Ordering::SeqCst);
+    }
+
+    /// Append a leaf update, tagging it with the next write version and updating
+    /// the index to point at the new record. Rolls over to a new append file
+    /// when the current one is full.
+    pub fn append(&self, leaf_key: Hash, account_hash: Hash) -> io::Result<u64> {
+        let write_version = self.write_version.fetch_add(1, Ordering::SeqCst) + 1;
+
+        // Resolve the target file, growing the Vec only when the tail file is
+        // full. The Vec lock is released before the mmap write below, so an
+        // append never blocks readers for the duration of the write.
+        let (file_id, file) = {
+            let files = self.files.read().unwrap();
+            let last = files.len() - 1;
+            if files[last].read().unwrap().remaining() >= RECORD_SIZE {
+                (last, files[last].clone())
+            } else {
+                drop(files);
+                let mut files = self.files.write().unwrap();
+                let file_id = files.len();
+                let new_file = AppendVec::create(&Self::file_path(&self.path, file_id))?;
+                files.push(Arc::new(RwLock::new(new_file)));
+                (file_id, files[file_id].clone())
+            }
+        };
+
+        let offset = file
+            .write()
+            .unwrap()
+            .append(&leaf_key, &account_hash, write_version);
+
+        self.index.write().unwrap().insert(leaf_key, (file_id, offset));
+        Ok(write_version)
+    }
+
+    /// Look up the latest `account_hash` committed for `leaf_key`.
+    pub fn get(&self, leaf_key: &Hash) -> Option<Hash> {
+        let (file_id, offset) = *self.index.read().unwrap().get(leaf_key)?;
+        let file = self.files.read().unwrap()[file_id].clone();
+        let (_, account_hash, _) = file.read().unwrap().read(offset);
+        Some(account_hash)
+    }
+
+    /// The latest `(leaf_key, account_hash)` for every key, for rebuilding a tree.
+    pub fn latest_leaves(&self) -> Vec<(Hash, Hash)> {
+        let entries: Vec<(Hash, (usize, usize))> = {
+            let index = self.index.read().unwrap();
+            index.iter().map(|(key, loc)| (*key, *loc)).collect()
+        };
+        let files = self.files.read().unwrap().clone();
+        entries
+            .into_iter()
+            .map(|(leaf_key, (file_id, offset))| {
+                let (_, account_hash, _) = files[file_id].read().unwrap().read(offset);
+                (leaf_key, account_hash)
+            })
+            .collect()
+    }
+
+    /// Flush every memory-mapped append file to disk.
+    pub fn flush(&self) -> io::Result<()> {
+        let files = self.files.read().unwrap().clone();
+        for file in files.iter() {
+            file.read().unwrap().flush()?;
+        }
+        Ok(())
+    }
+}
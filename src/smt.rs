@@ -1,31 +1,152 @@
 use solana_hash::Hash;
 use solana_sha256_hasher::hashv;
 use solana_sdk::{pubkey::Pubkey, account::Account};
-use bincode::serialize;
+use bincode::{serialize, deserialize};
 use serde::{Serialize, Deserialize};
-use std::collections::HashMap;
+use std::collections::HashSet;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::OnceLock;
 
+use rayon::prelude::*;
 
-/// Sparse Merkle Tree with fixed depth (256-bit address space)
+use crate::persistent_store::PersistentStore;
+
+
+/// A node in the versioned sparse Merkle tree.
+///
+/// Subtrees are shared between versions behind `Arc`, so an update clones only
+/// the nodes on the 256-bit path it touches and repoints every untouched child
+/// at the existing `Arc` (copy-on-write), as in the Aptos in-memory scratchpad.
 #[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum Node {
+    /// An internal node caching the hash of its two children.
+    Internal {
+        left: Arc<Node>,
+        right: Arc<Node>,
+        hash: Hash,
+    },
+    /// A populated leaf holding the hashed key and the account value hash.
+    Leaf { key: Hash, value_hash: Hash },
+    /// An empty subtree; its hash is read from `default_hashes()` by level.
+    Empty,
+}
+
+/// One entry in the shared version history: an immutable singly-linked list
+/// whose tail is shared between every tree derived from a common ancestor. An
+/// update prepends a link in O(1) and shares the whole prefix, instead of
+/// cloning the entire version vector on every commit.
+#[derive(Clone, Debug)]
+struct VersionLink {
+    version: u64,
+    root: Arc<Node>,
+    parent: Option<Arc<VersionLink>>,
+}
+
+/// Sparse Merkle Tree with fixed depth (256-bit address space).
+///
+/// Each `insert`/`update` produces a new logical version that shares all
+/// untouched subtrees with the previous one. `history` is a persistent linked
+/// list of the shared root committed at every version through this lineage; see
+/// [`prune_history`](SparseMerkleTree::prune_history) to bound its depth.
+#[derive(Clone, Debug)]
 pub struct SparseMerkleTree {
-    pub nodes: HashMap<Hash, Hash>, // Internal nodes
-    pub leaves: HashMap<Hash, Hash>, // Leaf nodes mapping (Pubkey hash → Account hash)
-    pub root: Hash, // Root of the tree
+    pub root: Arc<Node>, // Root of the current version
+    pub version: u64, // Monotonic version of the current root
+    history: Option<Arc<VersionLink>>, // Shared roots, newest first
+    store: Option<Arc<PersistentStore>>, // Optional on-disk backing store
 }
 
 impl SparseMerkleTree {
 
     const ZERO_HASH: Hash = Hash::new_from_array([0; 32]); // Hash constant for empty accounts
 
+    /// Placeholder hash for an empty leaf (and, at level 0, an empty subtree).
+    /// Kept identical to [`ZERO_HASH`] so empty accounts and absent keys share a
+    /// single representation across the whole tree.
+    const EMPTY_LEAF: Hash = Self::ZERO_HASH;
+
+    /// Precomputed default subtree hashes. Entry `0` is the empty-leaf hash and
+    /// entry `i + 1` is `hash(table[i], table[i])`, so index `i` holds the hash
+    /// of an all-empty subtree of height `i`.
+    fn default_hashes() -> &'static [Hash; 257] {
+        static TABLE: OnceLock<[Hash; 257]> = OnceLock::new();
+        TABLE.get_or_init(|| {
+            let mut table = [SparseMerkleTree::EMPTY_LEAF; 257];
+            for level in 0..256 {
+                table[level + 1] = SparseMerkleTree::hash_nodes(&table[level], &table[level]);
+            }
+            table
+        })
+    }
+
     pub fn new() -> Self {
+        let root = Arc::new(Node::Empty);
+        let history = Some(Arc::new(VersionLink {
+            version: 0,
+            root: root.clone(),
+            parent: None,
+        }));
         SparseMerkleTree {
-            nodes: HashMap::new(),
-            leaves: HashMap::new(),
-            root: Hash::default(),
+            history,
+            root,
+            version: 0,
+            store: None,
         }
     }
 
+    /// Prepend a committed root to the shared history, sharing the existing
+    /// prefix rather than copying it.
+    fn link_version(&self, version: u64, root: &Arc<Node>) -> Option<Arc<VersionLink>> {
+        Some(Arc::new(VersionLink {
+            version,
+            root: root.clone(),
+            parent: self.history.clone(),
+        }))
+    }
+
+    /// Find the shared root committed at `version`, walking the history list.
+    fn version_root(&self, version: u64) -> Option<&Arc<Node>> {
+        let mut link = self.history.as_deref();
+        while let Some(current) = link {
+            if current.version == version {
+                return Some(&current.root);
+            }
+            link = current.parent.as_deref();
+        }
+        None
+    }
+
+    /// Bound the retained history to the `keep` most recent versions, dropping
+    /// the older tail so a long-running store does not retain every root
+    /// forever. Keeping `0` clears the history entirely.
+    pub fn prune_history(&mut self, keep: usize) {
+        if keep == 0 {
+            self.history = None;
+            return;
+        }
+        let mut retained = Vec::with_capacity(keep);
+        let mut link = self.history.clone();
+        while let Some(current) = link {
+            retained.push((current.version, current.root.clone()));
+            if retained.len() == keep {
+                break;
+            }
+            link = current.parent.clone();
+        }
+        // Rebuild oldest-first so the dropped tail is released.
+        let mut parent = None;
+        for (version, root) in retained.into_iter().rev() {
+            parent = Some(Arc::new(VersionLink {
+                version,
+                root,
+                parent,
+            }));
+        }
+        self.history = parent;
+    }
+
     /// Compute a hash for an account
     fn hash_account(account: &Account) -> Hash {
         let account_bytes = serialize(account).unwrap();
@@ -37,75 +158,466 @@ impl SparseMerkleTree {
         hashv(&[pubkey.as_ref()])
     }
 
+    /// Domain-separation prefix for an internal node digest, keeping
+    /// `hash(left || right)` distinguishable in format from a leaf digest as in
+    /// the Diem/Aptos scheme (guards against leaf/internal second-preimage).
+    const INTERNAL_DOMAIN: &'static [u8] = b"SMTInternalNode";
+
+    /// Domain-separation prefix for a populated leaf digest.
+    const LEAF_DOMAIN: &'static [u8] = b"SMTLeafNode";
+
     /// Compute a parent node hash
     fn hash_nodes(left: &Hash, right: &Hash) -> Hash {
-        hashv(&[left.as_ref(), right.as_ref()])
+        hashv(&[Self::INTERNAL_DOMAIN, left.as_ref(), right.as_ref()])
     }
 
-    pub fn insert(&mut self, pubkey: Pubkey, account: &Account) {
+    /// Digest of a populated leaf: `hash(leaf_key || value_hash)`.
+    fn hash_leaf(leaf_key: &Hash, value_hash: &Hash) -> Hash {
+        hashv(&[Self::LEAF_DOMAIN, leaf_key.as_ref(), value_hash.as_ref()])
+    }
+
+    /// Read bit `level` of a 256-bit key, with level 0 the least significant
+    /// bit. Level `i` decides left/right when folding a proof up level `i`.
+    fn key_bit(key: &Hash, level: usize) -> u8 {
+        (key.as_ref()[level / 8] >> (level % 8)) & 1
+    }
+
+    /// Value hash for an account, collapsing a zero-lamport empty account into
+    /// the empty-leaf placeholder.
+    fn value_hash(account: &Account) -> Hash {
+        if account.lamports == 0 && account.data.is_empty() {
+            Self::EMPTY_LEAF
+        } else {
+            Self::hash_account(account)
+        }
+    }
+
+    /// Hash of `node` interpreted as a subtree of height `level`.
+    fn node_hash(node: &Arc<Node>, level: usize) -> Hash {
+        match &**node {
+            Node::Internal { hash, .. } => *hash,
+            Node::Leaf { key, value_hash } => {
+                if *value_hash == Self::EMPTY_LEAF {
+                    Self::EMPTY_LEAF
+                } else {
+                    Self::hash_leaf(key, value_hash)
+                }
+            }
+            Node::Empty => Self::default_hashes()[level],
+        }
+    }
+
+    /// Copy-on-write update of the height-`level` subtree rooted at `node`,
+    /// placing `value_hash` at the leaf addressed by `key`. Only nodes on the
+    /// path are cloned; the off-path child of each cloned internal node keeps
+    /// pointing at the existing shared `Arc`.
+    fn update_node(node: &Arc<Node>, level: usize, key: &Hash, value_hash: Hash) -> Arc<Node> {
+        if level == 0 {
+            return Arc::new(Node::Leaf {
+                key: *key,
+                value_hash,
+            });
+        }
+
+        let (left, right) = match &**node {
+            Node::Internal { left, right, .. } => (left.clone(), right.clone()),
+            _ => (Arc::new(Node::Empty), Arc::new(Node::Empty)),
+        };
+
+        let (new_left, new_right) = if Self::key_bit(key, level - 1) == 0 {
+            (Self::update_node(&left, level - 1, key, value_hash), right)
+        } else {
+            (left, Self::update_node(&right, level - 1, key, value_hash))
+        };
+
+        let hash = Self::hash_nodes(
+            &Self::node_hash(&new_left, level - 1),
+            &Self::node_hash(&new_right, level - 1),
+        );
+
+        Arc::new(Node::Internal {
+            left: new_left,
+            right: new_right,
+            hash,
+        })
+    }
+
+    /// Produce a new version that commits `account` at `pubkey` while sharing
+    /// every untouched subtree with `self`. The returned handle carries the
+    /// extended version history and leaves `self` unmodified, giving callers a
+    /// cheap snapshot to execute speculative transactions against.
+    pub fn update(&self, pubkey: Pubkey, account: &Account) -> SparseMerkleTree {
         let leaf_key = Self::hash_key(&pubkey);
-        let leaf_hash = if account.lamports == 0 && account.data.is_empty() {
-            println!("🟡 Inserting empty account with ZERO_HASH: {:?}", pubkey);
-            Self::ZERO_HASH // Use predefined ZERO_HASH
+        let root = Self::update_node(&self.root, 256, &leaf_key, Self::value_hash(account));
+        let version = self.version + 1;
+        let history = self.link_version(version, &root);
+
+        SparseMerkleTree {
+            root,
+            version,
+            history,
+            store: self.store.clone(),
+        }
+    }
+
+    /// Commit `account` at `pubkey` in place, advancing to a new version.
+    pub fn insert(&mut self, pubkey: Pubkey, account: &Account) {
+        *self = self.update(pubkey, account);
+    }
+
+    /// Minimum number of pending updates in a subtree before its two branches
+    /// are recomputed on separate rayon tasks rather than serially.
+    const BATCH_PARALLEL_THRESHOLD: usize = 64;
+
+    /// Copy-on-write update of a whole batch of leaves under the height-`level`
+    /// subtree rooted at `node`. `updates` holds `(leaf_key, value_hash)` pairs
+    /// in input order; for a repeated key the last write wins. Each affected
+    /// internal node is rebuilt exactly once — the recursion visits the union
+    /// of the touched paths, deduplicating shared ancestors — and independent
+    /// branches recompute in parallel once a subtree holds enough work.
+    fn update_node_batch(node: &Arc<Node>, level: usize, updates: &[(Hash, Hash)]) -> Arc<Node> {
+        if updates.is_empty() {
+            return node.clone();
+        }
+        if level == 0 {
+            let (key, value_hash) = *updates.last().unwrap();
+            return Arc::new(Node::Leaf { key, value_hash });
+        }
+
+        let (left, right) = match &**node {
+            Node::Internal { left, right, .. } => (left.clone(), right.clone()),
+            _ => (Arc::new(Node::Empty), Arc::new(Node::Empty)),
+        };
+
+        let mut left_updates = Vec::new();
+        let mut right_updates = Vec::new();
+        for &update in updates {
+            if Self::key_bit(&update.0, level - 1) == 0 {
+                left_updates.push(update);
+            } else {
+                right_updates.push(update);
+            }
+        }
+
+        let (new_left, new_right) = if updates.len() >= Self::BATCH_PARALLEL_THRESHOLD {
+            rayon::join(
+                || Self::update_node_batch(&left, level - 1, &left_updates),
+                || Self::update_node_batch(&right, level - 1, &right_updates),
+            )
         } else {
-            Self::hash_account(account) // Normal hashing
+            (
+                Self::update_node_batch(&left, level - 1, &left_updates),
+                Self::update_node_batch(&right, level - 1, &right_updates),
+            )
         };
-    
-        // If the account is empty and already exists, do nothing
-        if let Some(existing_hash) = self.leaves.get(&leaf_key) {
-            if *existing_hash == Self::ZERO_HASH {
-                println!("⚠️ Skipping update for empty account: {:?}", pubkey);
-                return; // Do not update the root
+
+        let hash = Self::hash_nodes(
+            &Self::node_hash(&new_left, level - 1),
+            &Self::node_hash(&new_right, level - 1),
+        );
+
+        Arc::new(Node::Internal {
+            left: new_left,
+            right: new_right,
+            hash,
+        })
+    }
+
+    /// Commit a batch of account updates in a single version bump.
+    ///
+    /// Leaf hashes are computed in parallel with rayon, then one recursive pass
+    /// splits the updates by key bit and rehashes the touched paths from the
+    /// leaves upward, so each shared ancestor node is hashed exactly once
+    /// instead of redoing a full root-to-leaf pass per key. The result is identical to applying the entries one at a time.
+    pub fn insert_batch(&mut self, entries: &[(Pubkey, Account)]) {
+        if entries.is_empty() {
+            return;
+        }
+
+        let updates: Vec<(Hash, Hash)> = entries
+            .par_iter()
+            .map(|(pubkey, account)| (Self::hash_key(pubkey), Self::value_hash(account)))
+            .collect();
+
+        let root = Self::update_node_batch(&self.root, 256, &updates);
+        self.version += 1;
+        self.history = self.link_version(self.version, &root);
+        self.root = root;
+    }
+
+    /// Rebuild a leaf directly from its `(leaf_key, value_hash)` pair, used when
+    /// replaying a persistent log whose records carry hashes rather than whole
+    /// accounts.
+    fn insert_value_hash(&mut self, leaf_key: Hash, value_hash: Hash) {
+        let root = Self::update_node(&self.root, 256, &leaf_key, value_hash);
+        self.version += 1;
+        self.history = self.link_version(self.version, &root);
+        self.root = root;
+    }
+
+    /// The current `(leaf_key, value_hash)` of every populated leaf.
+    fn leaf_values(&self) -> Vec<(Hash, Hash)> {
+        let mut out = Vec::new();
+        Self::collect_values(&self.root, &mut out);
+        out
+    }
+
+    fn collect_values(node: &Arc<Node>, out: &mut Vec<(Hash, Hash)>) {
+        match &**node {
+            Node::Internal { left, right, .. } => {
+                Self::collect_values(left, out);
+                Self::collect_values(right, out);
+            }
+            Node::Leaf { key, value_hash } if *value_hash != Self::EMPTY_LEAF => {
+                out.push((*key, *value_hash));
             }
+            _ => {}
+        }
+    }
+
+    /// Open a tree backed by the persistent store at `path`, replaying any
+    /// existing append logs to restore the root (see [`recover`]). A fresh
+    /// directory yields an empty tree wired to the new store.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let store = Arc::new(PersistentStore::open(path)?);
+        let mut tree = SparseMerkleTree::new();
+        for (leaf_key, value_hash) in store.latest_leaves() {
+            tree.insert_value_hash(leaf_key, value_hash);
+        }
+        tree.store = Some(store);
+        Ok(tree)
+    }
+
+    /// Append every populated leaf of the current version to the backing store
+    /// and flush the memory-mapped files to disk. No-op when the tree has no
+    /// store attached.
+    pub fn flush(&self) -> io::Result<()> {
+        let Some(store) = &self.store else {
+            return Ok(());
+        };
+        for (leaf_key, value_hash) in self.leaf_values() {
+            store.append(leaf_key, value_hash)?;
         }
-    
-        self.leaves.insert(leaf_key, leaf_hash);
-    
-        // Only update the root if it is not an empty account
-        if leaf_hash != Self::ZERO_HASH {
-            self.update_path(leaf_key, leaf_hash);
+        store.flush()
+    }
+
+    /// Recover a tree from the append logs at `path`: replay the logs, keep the
+    /// highest-write-version record per leaf, recompute the node hashes, and
+    /// restore the root.
+    pub fn recover(path: impl AsRef<Path>) -> io::Result<Self> {
+        Self::open(path)
+    }
+
+    /// Retrieve the Merkle root of the current version.
+    pub fn get_root(&self) -> Hash {
+        Self::node_hash(&self.root, 256)
+    }
+
+    /// Alias for [`get_root`]: the root the proofs fold up to.
+    pub fn compute_root(&self) -> Hash {
+        self.get_root()
+    }
+
+    /// Retrieve the Merkle root committed at `version`, if it exists.
+    pub fn get_root_at(&self, version: u64) -> Option<Hash> {
+        self.version_root(version)
+            .map(|root| Self::node_hash(root, 256))
+    }
+
+    /// Collect the 256 sibling hashes from leaf to root along `leaf_key`,
+    /// substituting the matching `default_hashes()` entry for every empty branch.
+    fn proof_from(root: &Arc<Node>, leaf_key: &Hash) -> Vec<Hash> {
+        let mut proof = vec![Hash::default(); 256];
+        let mut node = root.clone();
+
+        for level in (1..=256).rev() {
+            let (left, right) = match &*node {
+                Node::Internal { left, right, .. } => (left.clone(), right.clone()),
+                _ => (Arc::new(Node::Empty), Arc::new(Node::Empty)),
+            };
+
+            if Self::key_bit(leaf_key, level - 1) == 0 {
+                proof[level - 1] = Self::node_hash(&right, level - 1);
+                node = left;
+            } else {
+                proof[level - 1] = Self::node_hash(&left, level - 1);
+                node = right;
+            }
         }
+
+        proof
     }
-    
 
-    /// Update a leaf and propagate changes to the root
-    fn update_path(&mut self, mut key: Hash, mut value: Hash) {
-        for _ in 0..256 {
-            let sibling = self.nodes.get(&key).copied().unwrap_or(Hash::default());
-            let parent_hash = if key.as_ref()[0] & 1 == 0 {
-                Self::hash_nodes(&value, &sibling)
+    /// Generate a sparse Merkle proof for an account in the current version.
+    ///
+    /// The returned vector holds the 256 sibling hashes from leaf to root, each
+    /// empty branch substituted with the matching `default_hashes()` entry. The
+    /// proof verifies against [`get_root`] and is independently checkable
+    /// off-tree via [`verify_proof`]; a key with no populated leaf yields a
+    /// non-inclusion proof.
+    pub fn generate_proof(&self, pubkey: &Pubkey) -> Option<Vec<Hash>> {
+        Some(Self::proof_from(&self.root, &Self::hash_key(pubkey)))
+    }
+
+    /// Generate a proof against the root committed at `version`.
+    pub fn generate_proof_at(&self, version: u64, pubkey: &Pubkey) -> Option<Vec<Hash>> {
+        self.version_root(version)
+            .map(|root| Self::proof_from(root, &Self::hash_key(pubkey)))
+    }
+
+    /// Verify an inclusion (or non-inclusion) proof for `pubkey` against `root`.
+    ///
+    /// Pass `Some(account)` to prove the account is committed under `root`, or
+    /// `None` (equivalently a zero-lamport empty account) to prove it is absent
+    /// — a non-inclusion proof is just a normal proof whose leaf value is the
+    /// empty-leaf placeholder. The leaf digest is folded upward for 256 levels,
+    /// reading bit `i` of the leaf key at level `i`.
+    pub fn verify_proof(
+        root: &Hash,
+        pubkey: &Pubkey,
+        account: Option<&Account>,
+        proof: &[Hash],
+    ) -> bool {
+        if proof.len() != 256 {
+            return false;
+        }
+
+        let leaf_key = Self::hash_key(pubkey);
+        let value_hash = match account {
+            Some(account) => Self::value_hash(account),
+            None => Self::EMPTY_LEAF,
+        };
+
+        let mut running = if value_hash == Self::EMPTY_LEAF {
+            Self::EMPTY_LEAF
+        } else {
+            Self::hash_leaf(&leaf_key, &value_hash)
+        };
+
+        for (level, sibling) in proof.iter().enumerate() {
+            running = if Self::key_bit(&leaf_key, level) == 0 {
+                Self::hash_nodes(&running, sibling)
             } else {
-                Self::hash_nodes(&sibling, &value)
+                Self::hash_nodes(sibling, &running)
             };
+        }
+
+        &running == root
+    }
 
-            self.nodes.insert(key, value);
-            value = parent_hash;
-            key = Self::hash_nodes(&key, &Hash::default()); // Move up in tree
+    /// Build a balanced binary Merkle tree bottom-up over `leaves`, sorted by
+    /// leaf key. Adjacent leaves are paired with `hash(left, right)`, a lone odd
+    /// node is promoted unchanged, and an empty set hashes to `Hash::default()`.
+    fn accounts_hash_from(mut leaves: Vec<(Hash, Hash)>) -> Hash {
+        if leaves.is_empty() {
+            return Hash::default();
         }
+        leaves.sort_by(|a, b| a.0.as_ref().cmp(b.0.as_ref()));
 
-        self.root = value;
+        let mut level: Vec<Hash> = leaves.into_iter().map(|(_, value_hash)| value_hash).collect();
+        while level.len() > 1 {
+            let mut next = Vec::with_capacity(level.len().div_ceil(2));
+            let mut i = 0;
+            while i < level.len() {
+                if i + 1 < level.len() {
+                    next.push(Self::hash_nodes(&level[i], &level[i + 1]));
+                    i += 2;
+                } else {
+                    next.push(level[i]); // lone odd node promoted unchanged
+                    i += 1;
+                }
+            }
+            level = next;
+        }
+        level[0]
     }
 
-    /// Retrieve the Merkle root
-    pub fn get_root(&self) -> Hash {
-        self.root
+    /// Deterministic accounts hash over the full sorted leaf set.
+    ///
+    /// Distinct from the incremental sparse root, this is the "state hash" a
+    /// caller can place into a snapshot and verify independently after load.
+    pub fn compute_accounts_hash(&self) -> Hash {
+        Self::accounts_hash_from(self.leaf_values())
     }
 
-    /// Generate a Merkle proof for an account
-    pub fn generate_proof(&self, pubkey: &Pubkey) -> Option<Vec<Hash>> {
-        let mut key = Self::hash_key(pubkey);
-        let mut proof = Vec::new();
+    /// Accounts hash over only the accounts in `changed`, using the same
+    /// balanced structure, so callers can cheaply attest to per-slot deltas.
+    pub fn compute_accounts_delta_hash(&self, changed: &[Pubkey]) -> Hash {
+        let keys: HashSet<Hash> = changed.iter().map(Self::hash_key).collect();
+        let leaves = self
+            .leaf_values()
+            .into_iter()
+            .filter(|(leaf_key, _)| keys.contains(leaf_key))
+            .collect();
+        Self::accounts_hash_from(leaves)
+    }
 
-        for _ in 0..256 {
-            let sibling = self.nodes.get(&key).copied().unwrap_or(Hash::default());
-            proof.push(sibling);
-            key = Self::hash_nodes(&key, &Hash::default());
+    /// Rebuild a tree handle around a single committed root, starting a fresh
+    /// one-entry history (used when restoring a snapshot).
+    fn from_root(root: Arc<Node>, version: u64) -> Self {
+        let history = Some(Arc::new(VersionLink {
+            version,
+            root: root.clone(),
+            parent: None,
+        }));
+        SparseMerkleTree {
+            root,
+            version,
+            history,
+            store: None,
         }
+    }
+
+    /// Serialize the current version together with its recorded accounts hash.
+    ///
+    /// Only the current root is written — not the version history — so the
+    /// snapshot is the size of a single tree rather than `versions × tree`.
+    pub fn serialize_snapshot(&self) -> Vec<u8> {
+        let wire = SnapshotWire {
+            root: self.root.clone(),
+            version: self.version,
+            accounts_hash: self.compute_accounts_hash(),
+        };
+        serialize(&wire).unwrap()
+    }
+
+    /// Restore a [`Snapshot`] produced by [`serialize_snapshot`], returning an
+    /// error instead of panicking on malformed or untrusted bytes.
+    pub fn deserialize_snapshot(bytes: &[u8]) -> io::Result<Snapshot> {
+        let wire: SnapshotWire = deserialize(bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        Ok(Snapshot {
+            tree: SparseMerkleTree::from_root(wire.root, wire.version),
+            accounts_hash: wire.accounts_hash,
+        })
+    }
+}
 
-        Some(proof)
+impl Default for SparseMerkleTree {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
+/// On-the-wire snapshot: the current root plus the accounts hash recorded at
+/// capture time. Keeping only the current root (not the shared history) bounds
+/// the snapshot to a single tree's worth of nodes.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct SnapshotWire {
+    root: Arc<Node>,
+    version: u64,
+    accounts_hash: Hash,
+}
+
+/// A restored snapshot: the tree rebuilt from the captured root plus the
+/// accounts hash recorded at capture time, so the state can be independently
+/// verified after load.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    pub tree: SparseMerkleTree,
+    pub accounts_hash: Hash,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -125,11 +637,11 @@ mod tests {
     #[test]
     fn test_insert_and_generate_root() {
         let mut smt = SparseMerkleTree::new();
-        
+
         let pubkey1 = Keypair::new().pubkey();
         let account1 = create_example_account(pubkey1);
         smt.insert(pubkey1, &account1);
-        
+
         let root1 = smt.get_root();
         println!("🌳 Root after first insertion: {:?}", root1);
         assert_ne!(root1, Hash::default(), "Root should not be default after insertion");
@@ -160,7 +672,7 @@ mod tests {
     fn test_proof_for_non_existent_key() {
         let smt = SparseMerkleTree::new();
         let random_pubkey = Keypair::new().pubkey();
-        
+
         let proof = smt.generate_proof(&random_pubkey);
         assert!(proof.is_some(), "Proof should be empty but not None for non-existent keys");
         println!("⚠️ Proof for non-existent key: {:?}", proof.unwrap());
@@ -173,7 +685,7 @@ mod tests {
         let pubkey = Keypair::new().pubkey();
         let mut account = create_example_account(pubkey);
         smt.insert(pubkey, &account);
-        
+
         let root_before = smt.get_root();
         println!("🌳 Root before update: {:?}", root_before);
 
@@ -188,16 +700,15 @@ mod tests {
     #[test]
     fn test_insert_empty_account_does_not_update_root() {
         let mut smt = SparseMerkleTree::new();
-    
+
         // Insert a normal account
         let pubkey1 = Keypair::new().pubkey();
         let account1 = create_example_account(pubkey1);
         smt.insert(pubkey1, &account1);
-    
+
         let root_before = smt.get_root();
         println!("🌳 Root before inserting empty account: {:?}", root_before);
-        println!("📝 Tree before inserting empty account: {:?}", smt.nodes);
-    
+
         // Insert an empty account
         let empty_pubkey = Keypair::new().pubkey();
         let empty_account = Account {
@@ -207,15 +718,151 @@ mod tests {
             rent_epoch: 0,
             owner: empty_pubkey,
         };
-    
+
         smt.insert(empty_pubkey, &empty_account); // Insert empty account
-    
+
         let root_after = smt.get_root();
         println!("🌳 Root after inserting empty account: {:?}", root_after);
-        println!("📝 Tree after inserting empty account: {:?}", smt.nodes);
-    
+
         // Root should remain the same since we added an "empty" account with ZERO_HASH
         assert_eq!(root_before, root_after, "Root should NOT change after inserting an empty account");
     }
-    
-}
\ No newline at end of file
+
+    #[test]
+    fn test_verify_inclusion_proof() {
+        let mut smt = SparseMerkleTree::new();
+
+        let pubkey = Keypair::new().pubkey();
+        let account = create_example_account(pubkey);
+        smt.insert(pubkey, &account);
+
+        let other = Keypair::new().pubkey();
+        smt.insert(other, &create_example_account(other));
+
+        let root = smt.get_root();
+        let proof = smt.generate_proof(&pubkey).expect("proof should exist");
+        println!("✅ Verifying inclusion proof for {:?}", pubkey);
+        assert!(
+            SparseMerkleTree::verify_proof(&root, &pubkey, Some(&account), &proof),
+            "Inclusion proof should verify against the computed root"
+        );
+    }
+
+    #[test]
+    fn test_verify_non_inclusion_proof() {
+        let mut smt = SparseMerkleTree::new();
+
+        let present = Keypair::new().pubkey();
+        smt.insert(present, &create_example_account(present));
+
+        let absent = Keypair::new().pubkey();
+        let root = smt.get_root();
+        let proof = smt.generate_proof(&absent).expect("proof should exist");
+        println!("🚫 Verifying non-inclusion proof for {:?}", absent);
+        assert!(
+            SparseMerkleTree::verify_proof(&root, &absent, None, &proof),
+            "Non-inclusion proof should verify for an absent account"
+        );
+    }
+
+    #[test]
+    fn test_versions_share_history_and_preserve_roots() {
+        let base = SparseMerkleTree::new();
+
+        let pubkey1 = Keypair::new().pubkey();
+        let v1 = base.update(pubkey1, &create_example_account(pubkey1));
+
+        let pubkey2 = Keypair::new().pubkey();
+        let v2 = v1.update(pubkey2, &create_example_account(pubkey2));
+
+        println!("🧬 Versions: base={} v1={} v2={}", base.version, v1.version, v2.version);
+
+        // The older handle keeps its root; the newer version exposes the past.
+        assert_eq!(v1.get_root(), v2.get_root_at(1).unwrap(), "Historical root should match the v1 handle");
+        assert_ne!(v1.get_root(), v2.get_root(), "A new version must produce a new root");
+
+        // A proof generated against the historical root still verifies.
+        let proof = v2.generate_proof_at(1, &pubkey1).unwrap();
+        assert!(
+            SparseMerkleTree::verify_proof(
+                &v2.get_root_at(1).unwrap(),
+                &pubkey1,
+                Some(&create_example_account(pubkey1)),
+                &proof,
+            ),
+            "Proof against a historical root should verify"
+        );
+    }
+
+    #[test]
+    fn test_persistent_store_flush_and_recover() {
+        let dir = std::env::temp_dir().join(format!("smt-store-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let mut smt = SparseMerkleTree::open(&dir).expect("store should open");
+        let pubkey = Keypair::new().pubkey();
+        smt.insert(pubkey, &create_example_account(pubkey));
+        let root = smt.get_root();
+        smt.flush().expect("flush should succeed");
+
+        // Re-opening replays the append logs and restores the same root.
+        let recovered = SparseMerkleTree::recover(&dir).expect("recover should succeed");
+        println!("💾 Recovered root: {:?}", recovered.get_root());
+        assert_eq!(root, recovered.get_root(), "Recovered root should match the flushed root");
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_accounts_hash_and_snapshot_roundtrip() {
+        let mut smt = SparseMerkleTree::new();
+        let pubkey1 = Keypair::new().pubkey();
+        let pubkey2 = Keypair::new().pubkey();
+        smt.insert(pubkey1, &create_example_account(pubkey1));
+        smt.insert(pubkey2, &create_example_account(pubkey2));
+
+        let accounts_hash = smt.compute_accounts_hash();
+        assert_ne!(accounts_hash, Hash::default(), "Accounts hash should be set for a non-empty tree");
+
+        // The delta over a single account is just that leaf promoted to the root.
+        let delta = smt.compute_accounts_delta_hash(&[pubkey1]);
+        assert_ne!(delta, Hash::default(), "Delta hash should cover the changed account");
+
+        let bytes = smt.serialize_snapshot();
+        let snapshot = SparseMerkleTree::deserialize_snapshot(&bytes).expect("snapshot should decode");
+        println!("📦 Snapshot accounts hash: {:?}", snapshot.accounts_hash);
+        assert_eq!(snapshot.accounts_hash, accounts_hash, "Recorded accounts hash should round-trip");
+        assert_eq!(snapshot.tree.get_root(), smt.get_root(), "Tree root should round-trip");
+        assert_eq!(
+            snapshot.tree.compute_accounts_hash(),
+            accounts_hash,
+            "Restored tree should recompute the same accounts hash"
+        );
+    }
+
+    #[test]
+    fn test_insert_batch_matches_sequential() {
+        // Exceed BATCH_PARALLEL_THRESHOLD so the rayon::join branch is covered.
+        let entries: Vec<(Pubkey, Account)> = (0..128)
+            .map(|_| {
+                let pubkey = Keypair::new().pubkey();
+                (pubkey, create_example_account(pubkey))
+            })
+            .collect();
+
+        let mut sequential = SparseMerkleTree::new();
+        for (pubkey, account) in &entries {
+            sequential.insert(*pubkey, account);
+        }
+
+        let mut batched = SparseMerkleTree::new();
+        batched.insert_batch(&entries);
+
+        println!("⚡ Batch root: {:?}", batched.get_root());
+        assert_eq!(
+            batched.get_root(),
+            sequential.get_root(),
+            "Batch insert should match inserting one at a time"
+        );
+    }
+}
@@ -50,7 +50,7 @@ impl SparseMerkleTree {
             let sibling_index = index ^ 1; // Find the sibling (even/odd index)
             let sibling_hash = self.tree.get(&sibling_index).cloned().unwrap_or(Hash::default());
 
-            if index % 2 == 0 {
+            if index.is_multiple_of(2) {
                 current_hash = Self::hash_nodes(&current_hash, &sibling_hash);
             } else {
                 current_hash = Self::hash_nodes(&sibling_hash, &current_hash);
@@ -74,6 +74,12 @@ impl SparseMerkleTree {
     }
 }
 
+impl Default for SparseMerkleTree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;